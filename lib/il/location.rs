@@ -14,6 +14,10 @@
 //! references.
 
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use il::*;
 
 /// A location applied to a `Program`.
@@ -214,12 +218,619 @@ impl<'p> RefProgramLocation<'p> {
     pub fn advance_forward(&self) -> Result<Vec<RefProgramLocation<'p>>> {
         match self.function_location {
             // We are currently at an instruction
-            RefFunctionLocation::Instruction(block, instruction) => 
+            RefFunctionLocation::Instruction(block, instruction) =>
                 self.advance_instruction_forward(block, instruction),
             RefFunctionLocation::Edge(edge) => self.advance_edge_forward(edge),
             RefFunctionLocation::EmptyBlock(block) =>self.advance_empty_block_forward(block)
         }
     }
+
+
+    fn advance_instruction_backward(&self, block: &'p Block, instruction: &Instruction)
+    -> Result<Vec<RefProgramLocation<'p>>> {
+
+        let instructions = block.instructions();
+        for i in 0..instructions.len() {
+            // We found the instruction.
+            if instructions[i].index() == instruction.index() {
+                // Is there a previous instruction in this block?
+                if i > 0 {
+                    // Return the previous instruction
+                    let instruction = &instructions[i - 1];
+                    return Ok(vec![RefProgramLocation::new(self.function,
+                        RefFunctionLocation::Instruction(block, instruction))]);
+                }
+                // No previous instruction, return edges into the block
+                let edges = match self.function
+                                      .control_flow_graph()
+                                      .edges_in(block.index()) {
+                    Some(edges) => edges,
+                    None => bail!("Could not find block {} in function {}",
+                        block.index(),
+                        self.function.index().unwrap())
+                };
+                let mut locations = Vec::new();
+                for edge in edges {
+                    locations.push(RefProgramLocation::new(&self.function,
+                        RefFunctionLocation::Edge(edge)));
+                }
+                return Ok(locations)
+            }
+        }
+
+        Err(format!("Could not find instruction {} in block {} in function {}",
+            instruction.index(),
+            block.index(),
+            self.function.index().unwrap()).into())
+    }
+
+
+    fn advance_edge_backward(&self, edge: &'p Edge)
+    -> Result<Vec<RefProgramLocation<'p>>> {
+
+        let block = match self.function.block(edge.head()) {
+            Some(block) => block,
+            None => bail!("Could not find block {} in function {}",
+                edge.head(), self.function.index().unwrap())
+        };
+
+        let instructions = block.instructions();
+        if instructions.is_empty() {
+            Ok(vec![RefProgramLocation::new(self.function,
+                RefFunctionLocation::EmptyBlock(block))])
+        }
+        else {
+            Ok(vec![RefProgramLocation::new(self.function,
+                RefFunctionLocation::Instruction(block, &instructions[instructions.len() - 1]))])
+        }
+    }
+
+
+    fn advance_empty_block_backward(&self, block: &'p Block)
+    -> Result<Vec<RefProgramLocation<'p>>> {
+
+        let edges = match self.function
+                               .control_flow_graph()
+                               .edges_in(block.index()){
+            Some(edges) => edges,
+            None => bail!("Could not find block {} in function {}",
+                block.index(), self.function.index().unwrap())
+        };
+
+        let mut locations = Vec::new();
+        for edge in edges {
+
+            locations.push(RefProgramLocation::new(self.function,
+                RefFunctionLocation::Edge(edge)));
+        }
+
+        Ok(locations)
+    }
+
+
+    /// Advance the `RefProgramLocation` backward.
+    ///
+    /// This is the symmetric counterpart to `advance_forward`: it causes the
+    /// underlying `RefFunctionLocation` to reference the previous
+    /// `RefFunctionLocation`(s). Like `advance_forward`, this does not cross
+    /// call boundaries.
+    pub fn advance_backward(&self) -> Result<Vec<RefProgramLocation<'p>>> {
+        match self.function_location {
+            RefFunctionLocation::Instruction(block, instruction) =>
+                self.advance_instruction_backward(block, instruction),
+            RefFunctionLocation::Edge(edge) => self.advance_edge_backward(edge),
+            RefFunctionLocation::EmptyBlock(block) => self.advance_empty_block_backward(block)
+        }
+    }
+
+
+    /// If this location is an `Instruction` whose `Operation` is a `Brc` to
+    /// a constant address, return that address.
+    ///
+    /// `Brc` is Falcon's general branch operation, used for both intra-
+    /// function branches and calls, so a constant target here does not by
+    /// itself mean this is a call -- `advance_forward_call` still has to
+    /// check that the resolved address actually lands on another
+    /// function's entry before treating it as one.
+    fn call_target_address(&self) -> Option<u64> {
+        let instruction = self.instruction()?;
+        match *instruction.operation() {
+            Operation::Brc { ref target, .. } => match *target {
+                Expression::Constant(ref constant) => Some(constant.value()),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+
+    /// Is `location` the first `Instruction` of its function's entry block?
+    fn is_function_entry(location: &RefProgramLocation) -> bool {
+        let (block, instruction) = match *location.function_location() {
+            RefFunctionLocation::Instruction(block, instruction) => (block, instruction),
+            _ => return false
+        };
+
+        let entry_block_index = match location.function().control_flow_graph().entry() {
+            Some(entry_block_index) => entry_block_index,
+            None => return false
+        };
+
+        if block.index() != entry_block_index {
+            return false;
+        }
+
+        block.instructions()
+             .first()
+             .map(|first| first.index() == instruction.index())
+             .unwrap_or(false)
+    }
+
+
+    /// Advance the `RefProgramLocation` forward, following the target of a
+    /// call instruction across function boundaries.
+    ///
+    /// If this location is an `Instruction` whose `Operation` is a `Brc` to
+    /// a constant address, that address is resolved against `program` (the
+    /// same way `RefProgramLocation::from_address` resolves an address). If
+    /// the resolved location is the entry instruction of a *different*
+    /// function, this is a call: its entry location is returned alongside
+    /// the location(s) execution returns to once the callee completes --
+    /// the ordinary successor(s) of the call site, as given by
+    /// `advance_forward` -- so a caller can push/pop a call string while
+    /// walking across the call.
+    ///
+    /// `Brc` is also Falcon's ordinary intra-function branch, so a constant
+    /// target that resolves within this same function (or that is not a
+    /// function's entry instruction at all) is not a call; `Ok(None)` is
+    /// returned in that case, as well as when the target cannot be resolved
+    /// (for example, an indirect call) or this location is not a branch.
+    /// Callers should fall back to `advance_forward` whenever `Ok(None)` is
+    /// returned.
+    pub fn advance_forward_call(&self, program: &'p Program)
+    -> Result<Option<CallAdvance<'p>>> {
+
+        let target_address = match self.call_target_address() {
+            Some(target_address) => target_address,
+            None => return Ok(None)
+        };
+
+        let callee_entry = match RefProgramLocation::from_address(program, target_address) {
+            Some(callee_entry) => callee_entry,
+            None => return Ok(None)
+        };
+
+        if callee_entry.function().index() == self.function.index()
+            || !RefProgramLocation::is_function_entry(&callee_entry) {
+            return Ok(None);
+        }
+
+        Ok(Some(CallAdvance {
+            callee_entry: callee_entry,
+            return_locations: self.advance_forward()?
+        }))
+    }
+}
+
+
+impl<'p> RefProgramLocation<'p> {
+    /// The `(block_index, intra-block position)` of this location, used to
+    /// order locations within a single block for dominance purposes.
+    ///
+    /// An `Edge` is positioned just past the last instruction of its head
+    /// block, as it represents control leaving that block.
+    fn dominance_position(&self) -> Result<(u64, usize)> {
+        match self.function_location {
+            RefFunctionLocation::Instruction(block, instruction) => {
+                let instructions = block.instructions();
+                for (i, candidate) in instructions.iter().enumerate() {
+                    if candidate.index() == instruction.index() {
+                        return Ok((block.index(), i));
+                    }
+                }
+                bail!("Could not find instruction {} in block {} in function {}",
+                    instruction.index(), block.index(), self.function.index().unwrap())
+            },
+            RefFunctionLocation::EmptyBlock(block) => Ok((block.index(), 0)),
+            RefFunctionLocation::Edge(edge) => {
+                let block = match self.function.block(edge.head()) {
+                    Some(block) => block,
+                    None => bail!("Could not find block {} in function {}",
+                        edge.head(), self.function.index().unwrap())
+                };
+                let instructions = block.instructions();
+                // An empty head has no instructions to count past, but the
+                // edge still sits one position after the block's own
+                // `EmptyBlock` location (position 0), not on top of it.
+                let position = if instructions.is_empty() { 1 } else { instructions.len() };
+                Ok((block.index(), position))
+            }
+        }
+    }
+
+
+    /// Does `edge` leave a block with more than one outgoing edge?
+    ///
+    /// An `Edge` location's `dominance_position` collapses it onto its head
+    /// block, which is only a sound stand-in for block-level dominance when
+    /// the edge is its head's *only* way out: otherwise a sibling edge out
+    /// of the same block would wrongly be treated as dominating everything
+    /// the head block dominates, even locations only reachable via the
+    /// other branch.
+    fn edge_has_sibling(&self, edge: &Edge) -> Result<bool> {
+        match self.function.control_flow_graph().edges_out(edge.head()) {
+            Some(edges) => Ok(edges.len() > 1),
+            None => bail!("Could not find block {} in function {}",
+                edge.head(), self.function.index().unwrap())
+        }
+    }
+
+
+    /// The immediate-dominator map for this location's function, keyed and
+    /// valued by block index.
+    fn dominator_map(&self) -> Result<HashMap<u64, u64>> {
+        let cfg = self.function.control_flow_graph();
+        let entry = match cfg.entry() {
+            Some(entry) => entry,
+            None => bail!("Function {} has no entry block", self.function.index().unwrap())
+        };
+
+        let successors = |block_index: u64| -> Vec<u64> {
+            cfg.edges_out(block_index)
+               .map(|edges| edges.iter().map(|edge| edge.tail()).collect())
+               .unwrap_or_else(Vec::new)
+        };
+        let predecessors = |block_index: u64| -> Vec<u64> {
+            cfg.edges_in(block_index)
+               .map(|edges| edges.iter().map(|edge| edge.head()).collect())
+               .unwrap_or_else(Vec::new)
+        };
+
+        let rpo = reverse_postorder(entry, &successors);
+        Ok(compute_idom(entry, &rpo, &predecessors))
+    }
+
+
+    /// The immediate-post-dominator map for this location's function, keyed
+    /// and valued by block index.
+    ///
+    /// This runs the same Cooper-Harvey-Kennedy routine over the reversed
+    /// `control_flow_graph`, rooted at a virtual node joining every block
+    /// with no outgoing edges.
+    fn post_dominator_map(&self) -> Result<HashMap<u64, u64>> {
+        const VIRTUAL_EXIT: u64 = u64::max_value();
+
+        let cfg = self.function.control_flow_graph();
+
+        let exits: Vec<u64> = cfg.blocks()
+            .iter()
+            .map(|block| block.index())
+            .filter(|&block_index| {
+                cfg.edges_out(block_index)
+                   .map(|edges| edges.is_empty())
+                   .unwrap_or(true)
+            })
+            .collect();
+
+        let successors = |block_index: u64| -> Vec<u64> {
+            if block_index == VIRTUAL_EXIT {
+                return exits.clone();
+            }
+            cfg.edges_in(block_index)
+               .map(|edges| edges.iter().map(|edge| edge.head()).collect())
+               .unwrap_or_else(Vec::new)
+        };
+        let predecessors = |block_index: u64| -> Vec<u64> {
+            let mut preds: Vec<u64> = cfg.edges_out(block_index)
+                .map(|edges| edges.iter().map(|edge| edge.tail()).collect())
+                .unwrap_or_else(Vec::new);
+            if exits.contains(&block_index) {
+                preds.push(VIRTUAL_EXIT);
+            }
+            preds
+        };
+
+        let rpo = reverse_postorder(VIRTUAL_EXIT, &successors);
+        let mut idom = compute_idom(VIRTUAL_EXIT, &rpo, &predecessors);
+        idom.remove(&VIRTUAL_EXIT);
+        Ok(idom)
+    }
+
+
+    /// Does this location dominate `other`? Both locations must belong to
+    /// the same `Function`.
+    ///
+    /// If this location is an `Edge` out of a block with more than one
+    /// successor, it only dominates itself: block-level dominance of its
+    /// head block is not enough to show every path to `other` takes this
+    /// particular edge rather than a sibling one out of the same block.
+    pub fn dominates(&self, other: &RefProgramLocation<'p>) -> Result<bool> {
+        if self.function.index() != other.function.index() {
+            bail!("Cannot compare dominance of locations in different functions");
+        }
+
+        if let RefFunctionLocation::Edge(edge) = self.function_location {
+            if self.edge_has_sibling(edge)? {
+                return Ok(self.function_location == other.function_location);
+            }
+        }
+
+        let (self_block, self_position) = self.dominance_position()?;
+        let (other_block, other_position) = other.dominance_position()?;
+
+        if self_block == other_block {
+            return Ok(self_position <= other_position);
+        }
+
+        Ok(block_dominates(&self.dominator_map()?, self_block, other_block))
+    }
+
+
+    /// Does this location post-dominate `other`? Both locations must belong
+    /// to the same `Function`.
+    ///
+    /// As with `dominates`, an `Edge` out of a block with more than one
+    /// successor only post-dominates itself, for the same reason: the
+    /// block-level check can't tell this edge apart from a sibling one
+    /// leaving the same block.
+    pub fn post_dominates(&self, other: &RefProgramLocation<'p>) -> Result<bool> {
+        if self.function.index() != other.function.index() {
+            bail!("Cannot compare post-dominance of locations in different functions");
+        }
+
+        if let RefFunctionLocation::Edge(edge) = self.function_location {
+            if self.edge_has_sibling(edge)? {
+                return Ok(self.function_location == other.function_location);
+            }
+        }
+
+        let (self_block, self_position) = self.dominance_position()?;
+        let (other_block, other_position) = other.dominance_position()?;
+
+        if self_block == other_block {
+            return Ok(self_position >= other_position);
+        }
+
+        Ok(block_dominates(&self.post_dominator_map()?, self_block, other_block))
+    }
+
+
+    /// Get the immediate dominator of this location, if one exists.
+    ///
+    /// If this location is not the first location of its block (intra-block
+    /// position greater than zero), its immediate dominator is simply the
+    /// previous instruction in that same block, since straight-line code
+    /// within a block has no other predecessor.
+    ///
+    /// Otherwise this location is the first location of its block, and its
+    /// immediate dominator is the last instruction (or `EmptyBlock`) of its
+    /// block's immediate-dominator block in the `control_flow_graph`,
+    /// computed with the Cooper-Harvey-Kennedy iterative algorithm: blocks
+    /// are numbered in reverse postorder from the entry block, and each
+    /// block's immediate dominator is refined by intersecting its processed
+    /// predecessors until nothing changes. The entry block has no immediate
+    /// dominator, so its first location returns `None`.
+    pub fn immediate_dominator(&self) -> Result<Option<RefProgramLocation<'p>>> {
+        // An edge leaving an empty block has no instruction to fall back one
+        // position from; `dominance_position` places it just past the
+        // block's own `EmptyBlock` location, and that `EmptyBlock` location
+        // is exactly its immediate dominator.
+        if let RefFunctionLocation::Edge(edge) = self.function_location {
+            let block = match self.function.block(edge.head()) {
+                Some(block) => block,
+                None => bail!("Could not find block {} in function {}",
+                    edge.head(), self.function.index().unwrap())
+            };
+            if block.instructions().is_empty() {
+                return Ok(Some(RefProgramLocation::new(self.function,
+                    RefFunctionLocation::EmptyBlock(block))));
+            }
+        }
+
+        let (block_index, position) = self.dominance_position()?;
+
+        if position > 0 {
+            let block = match self.function.block(block_index) {
+                Some(block) => block,
+                None => bail!("Could not find block {} in function {}",
+                    block_index, self.function.index().unwrap())
+            };
+            let instructions = block.instructions();
+            return Ok(Some(RefProgramLocation::new(self.function,
+                RefFunctionLocation::Instruction(block, &instructions[position - 1]))));
+        }
+
+        let dominator_map = self.dominator_map()?;
+
+        let idom_block_index = match dominator_map.get(&block_index) {
+            Some(&idom) if idom != block_index => idom,
+            _ => return Ok(None)
+        };
+
+        let block = match self.function.block(idom_block_index) {
+            Some(block) => block,
+            None => bail!("Could not find block {} in function {}",
+                idom_block_index, self.function.index().unwrap())
+        };
+
+        let instructions = block.instructions();
+        let function_location = if instructions.is_empty() {
+            RefFunctionLocation::EmptyBlock(block)
+        } else {
+            RefFunctionLocation::Instruction(block, &instructions[instructions.len() - 1])
+        };
+
+        Ok(Some(RefProgramLocation::new(self.function, function_location)))
+    }
+}
+
+
+/// Compute a reverse-postorder block ordering from `entry`, following edges
+/// given by `successors`.
+fn reverse_postorder<F>(entry: u64, successors: &F) -> Vec<u64>
+    where F: Fn(u64) -> Vec<u64>
+{
+    fn visit<F>(block_index: u64, successors: &F, visited: &mut HashSet<u64>, postorder: &mut Vec<u64>)
+        where F: Fn(u64) -> Vec<u64>
+    {
+        if !visited.insert(block_index) {
+            return;
+        }
+        for successor in successors(block_index) {
+            visit(successor, successors, visited, postorder);
+        }
+        postorder.push(block_index);
+    }
+
+    let mut visited = HashSet::new();
+    let mut postorder = Vec::new();
+
+    visit(entry, successors, &mut visited, &mut postorder);
+
+    postorder.reverse();
+    postorder
+}
+
+
+/// Compute the immediate-dominator map for a control-flow graph, given its
+/// blocks in reverse postorder from `entry`, using the Cooper-Harvey-Kennedy
+/// iterative algorithm. `predecessors` abstracts over traversal direction,
+/// so the same routine computes both dominators and post-dominators.
+fn compute_idom<F>(entry: u64, rpo: &[u64], predecessors: &F) -> HashMap<u64, u64>
+    where F: Fn(u64) -> Vec<u64>
+{
+    let rpo_number: HashMap<u64, usize> = rpo.iter()
+        .enumerate()
+        .map(|(i, &block_index)| (block_index, i))
+        .collect();
+
+    let mut idom: HashMap<u64, u64> = HashMap::new();
+    idom.insert(entry, entry);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block_index in rpo {
+            if block_index == entry {
+                continue;
+            }
+
+            let mut new_idom = None;
+            for pred in predecessors(block_index) {
+                if !idom.contains_key(&pred) {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => pred,
+                    Some(current) => intersect(current, pred, &idom, &rpo_number)
+                });
+            }
+
+            if let Some(new_idom) = new_idom {
+                if idom.get(&block_index) != Some(&new_idom) {
+                    idom.insert(block_index, new_idom);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    idom
+}
+
+
+/// The two-finger intersection step of the Cooper-Harvey-Kennedy algorithm:
+/// walk both candidates up the (partially-built) dominator tree until they
+/// meet, using reverse-postorder number to decide which finger to advance.
+fn intersect(mut a: u64, mut b: u64, idom: &HashMap<u64, u64>, rpo_number: &HashMap<u64, usize>) -> u64 {
+    while a != b {
+        while rpo_number[&a] > rpo_number[&b] {
+            a = idom[&a];
+        }
+        while rpo_number[&b] > rpo_number[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+
+/// Does the block `dominator` dominate the block `block_index`, according
+/// to the given immediate-dominator map?
+fn block_dominates(idom: &HashMap<u64, u64>, dominator: u64, block_index: u64) -> bool {
+    let mut current = block_index;
+    loop {
+        if current == dominator {
+            return true;
+        }
+        match idom.get(&current) {
+            Some(&next) if next != current => current = next,
+            _ => return false
+        }
+    }
+}
+
+
+/// Compute the set of locations reachable from `start`, following edges
+/// given by `advance`, as owned `ProgramLocation`s.
+fn reachable<'p, F>(start: &RefProgramLocation<'p>, advance: F)
+-> Result<HashSet<ProgramLocation>>
+    where F: Fn(&RefProgramLocation<'p>) -> Result<Vec<RefProgramLocation<'p>>>
+{
+    let mut visited: HashSet<ProgramLocation> = HashSet::new();
+    let mut worklist = vec![start.clone()];
+
+    while let Some(location) = worklist.pop() {
+        let program_location: ProgramLocation = location.clone().into();
+        if !visited.insert(program_location) {
+            continue;
+        }
+        for next in advance(&location)? {
+            worklist.push(next);
+        }
+    }
+
+    Ok(visited)
+}
+
+
+/// Compute the program chop (slice) between `source` and `sink`.
+///
+/// This is the set of locations that lie on some control-flow path from
+/// `source` to `sink`: the intersection of the locations reachable forward
+/// from `source` (via `advance_forward`) with the locations reachable
+/// backward from `sink` (via `advance_backward`). The result is returned as
+/// owned `ProgramLocation`s so it survives independent of the borrowed
+/// `Program` used to compute it.
+pub fn chop<'p>(source: &RefProgramLocation<'p>, sink: &RefProgramLocation<'p>)
+-> Result<HashSet<ProgramLocation>> {
+
+    let forward = reachable(source, RefProgramLocation::advance_forward)?;
+    let backward = reachable(sink, RefProgramLocation::advance_backward)?;
+
+    Ok(forward.intersection(&backward).cloned().collect())
+}
+
+
+/// The result of following a call edge with
+/// `RefProgramLocation::advance_forward_call`.
+#[derive(Clone, Debug)]
+pub struct CallAdvance<'p> {
+    callee_entry: RefProgramLocation<'p>,
+    return_locations: Vec<RefProgramLocation<'p>>
+}
+
+
+impl<'p> CallAdvance<'p> {
+    /// The entry location of the callee this call resolves to.
+    pub fn callee_entry(&self) -> &RefProgramLocation<'p> {
+        &self.callee_entry
+    }
+
+    /// The location(s) execution returns to once the callee completes.
+    pub fn return_locations(&self) -> &[RefProgramLocation<'p>] {
+        &self.return_locations
+    }
 }
 
 
@@ -262,7 +873,13 @@ impl<'f> RefFunctionLocation<'f> {
 
 
 /// A location independent of any specific instance of `Program`.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+///
+/// `ProgramLocation` is totally ordered, first by `function_index`, then by
+/// its `FunctionLocation`, so locations can be used as keys in `BTreeMap`/
+/// `BTreeSet` side tables the way a `SectionAddress` keys maps elsewhere.
+/// This is a structural order based on position, not a reachability
+/// order -- see `is_before` for the latter.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct ProgramLocation {
     function_index: u64,
     function_location: FunctionLocation
@@ -283,6 +900,28 @@ impl ProgramLocation {
         };
         Some(RefProgramLocation::new(function, function_location))
     }
+
+
+    /// Can this location reach `other`, forward through the
+    /// `control_flow_graph`, within the same function?
+    ///
+    /// Unlike the structural `Ord` on `ProgramLocation`, which sorts by
+    /// position for use in sorted containers, this actually walks
+    /// `RefProgramLocation::advance_forward` from `self` and answers
+    /// whether `other` is in the reachable set -- raw index order does not
+    /// imply execution order (for example, across a loop back-edge).
+    pub fn is_before(&self, other: &ProgramLocation, program: &Program) -> Result<bool> {
+        if self.function_index != other.function_index {
+            return Ok(false);
+        }
+
+        let start = match self.apply(program) {
+            Some(start) => start,
+            None => bail!("Could not apply location to program")
+        };
+
+        Ok(reachable(&start, RefProgramLocation::advance_forward)?.contains(other))
+    }
 }
 
 
@@ -297,7 +936,11 @@ impl<'p> From<RefProgramLocation<'p>> for ProgramLocation {
 
 
 /// A location indepdent of any specific instance of `Function`.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+///
+/// `FunctionLocation` orders first by block index, then by intra-block
+/// position: an `Edge` sorts after every location of its head block, and an
+/// `EmptyBlock` sorts as the sole location of its (instruction-less) block.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum FunctionLocation {
     Instruction(u64, u64),
     Edge(u64, u64),
@@ -306,6 +949,22 @@ pub enum FunctionLocation {
 
 
 impl FunctionLocation {
+    /// `(block_index, tier, intra-block index)`, where `tier` distinguishes
+    /// the three variants so that no two unequal `FunctionLocation`s ever
+    /// produce the same key: a block's instructions (tier 0) sort before
+    /// its `EmptyBlock` location (tier 1, always 0-valued since an empty
+    /// block has no instructions), which sorts before the `Edge`s leaving
+    /// it (tier 2).
+    fn sort_key(&self) -> (u64, u8, u64) {
+        match *self {
+            FunctionLocation::Instruction(block_index, instruction_index) =>
+                (block_index, 0, instruction_index),
+            FunctionLocation::EmptyBlock(block_index) => (block_index, 1, 0),
+            FunctionLocation::Edge(head, tail) => (head, 2, tail)
+        }
+    }
+
+
     /// "Apply" this `FunctionLocation` to a `Function`, returning a
     /// `RefFunctionLocation`.
     pub fn apply<'f>(&self, function: &'f Function)
@@ -340,6 +999,20 @@ impl FunctionLocation {
 }
 
 
+impl PartialOrd for FunctionLocation {
+    fn partial_cmp(&self, other: &FunctionLocation) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl Ord for FunctionLocation {
+    fn cmp(&self, other: &FunctionLocation) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+
 impl<'f> From<RefFunctionLocation<'f>> for FunctionLocation {
     fn from(function_location: RefFunctionLocation) -> FunctionLocation {
         match function_location {
@@ -351,4 +1024,332 @@ impl<'f> From<RefFunctionLocation<'f>> for FunctionLocation {
                 FunctionLocation::EmptyBlock(block.index())
         }
     }
+}
+
+
+// Some of these tests exercise the graph algorithms underneath dominance
+// and post-dominance directly, against small hand-built graphs of `u64`
+// block indices; the rest build small all-empty-block `Function`s (or a
+// two-function `Program`) and go through `RefProgramLocation` and
+// `ProgramLocation` themselves.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a diamond-shaped, all-empty-block `Function`: block 0 (entry)
+    /// branches to 1 and 2, which both rejoin at 3.
+    fn diamond_function() -> Function {
+        let mut cfg = ControlFlowGraph::new();
+        for _ in 0..4 {
+            cfg.new_block().unwrap();
+        }
+        cfg.unconditional_edge(0, 1).unwrap();
+        cfg.unconditional_edge(0, 2).unwrap();
+        cfg.unconditional_edge(1, 3).unwrap();
+        cfg.unconditional_edge(2, 3).unwrap();
+        cfg.set_entry(0).unwrap();
+        Function::new(0, cfg)
+    }
+
+    /// Build a two-function `Program`: function 0's entry block ends in a
+    /// `Brc` to the address of function 1's (sole) instruction.
+    fn call_program() -> Program {
+        const CALLEE_ADDRESS: u64 = 0x1000;
+
+        let mut caller_cfg = ControlFlowGraph::new();
+        {
+            let block = caller_cfg.new_block().unwrap();
+            let instruction = block.brc(Expression::Constant(
+                Constant::new(CALLEE_ADDRESS, 64)));
+            instruction.set_address(Some(0x0));
+        }
+        caller_cfg.set_entry(0).unwrap();
+        let caller = Function::new(0, caller_cfg);
+
+        let mut callee_cfg = ControlFlowGraph::new();
+        {
+            let block = callee_cfg.new_block().unwrap();
+            let instruction = block.nop();
+            instruction.set_address(Some(CALLEE_ADDRESS));
+        }
+        callee_cfg.set_entry(0).unwrap();
+        let callee = Function::new(1, callee_cfg);
+
+        let mut program = Program::new();
+        program.add_function(caller);
+        program.add_function(callee);
+        program
+    }
+
+    /// Build the all-empty-block `Function` underlying the chop test below:
+    /// 0 -> 1 -> 2 -> 4 is the only path from 0 to 4; 1 -> 3 is a side
+    /// branch that never reaches 4.
+    fn chop_function() -> Function {
+        let mut cfg = ControlFlowGraph::new();
+        for _ in 0..5 {
+            cfg.new_block().unwrap();
+        }
+        cfg.unconditional_edge(0, 1).unwrap();
+        cfg.unconditional_edge(1, 2).unwrap();
+        cfg.unconditional_edge(1, 3).unwrap();
+        cfg.unconditional_edge(2, 4).unwrap();
+        cfg.set_entry(0).unwrap();
+        Function::new(0, cfg)
+    }
+
+    fn graph(edges: &[(u64, u64)]) -> (HashMap<u64, Vec<u64>>, HashMap<u64, Vec<u64>>) {
+        let mut out = HashMap::new();
+        let mut inn = HashMap::new();
+        for &(head, tail) in edges {
+            out.entry(head).or_insert_with(Vec::new).push(tail);
+            inn.entry(tail).or_insert_with(Vec::new).push(head);
+        }
+        (out, inn)
+    }
+
+    #[test]
+    fn compute_idom_diamond() {
+        // 1 -> 2, 1 -> 3, 2 -> 4, 3 -> 4
+        let (out, inn) = graph(&[(1, 2), (1, 3), (2, 4), (3, 4)]);
+        let successors = |b: u64| out.get(&b).cloned().unwrap_or_else(Vec::new);
+        let predecessors = |b: u64| inn.get(&b).cloned().unwrap_or_else(Vec::new);
+
+        let rpo = reverse_postorder(1, &successors);
+        let idom = compute_idom(1, &rpo, &predecessors);
+
+        assert_eq!(idom[&1], 1);
+        assert_eq!(idom[&2], 1);
+        assert_eq!(idom[&3], 1);
+        // 4 has two predecessors, 2 and 3, whose only common dominator is 1
+        assert_eq!(idom[&4], 1);
+        assert!(block_dominates(&idom, 1, 4));
+        assert!(!block_dominates(&idom, 2, 4));
+    }
+
+    #[test]
+    fn compute_idom_loop() {
+        // 1 -> 2, 2 -> 3, 3 -> 2 (back edge), 3 -> 4
+        let (out, inn) = graph(&[(1, 2), (2, 3), (3, 2), (3, 4)]);
+        let successors = |b: u64| out.get(&b).cloned().unwrap_or_else(Vec::new);
+        let predecessors = |b: u64| inn.get(&b).cloned().unwrap_or_else(Vec::new);
+
+        let rpo = reverse_postorder(1, &successors);
+        let idom = compute_idom(1, &rpo, &predecessors);
+
+        assert_eq!(idom[&1], 1);
+        assert_eq!(idom[&2], 1);
+        assert_eq!(idom[&3], 2);
+        assert_eq!(idom[&4], 3);
+        assert!(block_dominates(&idom, 1, 4));
+        assert!(block_dominates(&idom, 2, 4));
+        assert!(!block_dominates(&idom, 3, 1));
+    }
+
+    #[test]
+    fn post_dominator_map_with_multiple_exits() {
+        // 1 -> 2, 2 -> 3, 2 -> 4; 3 and 4 are both exit blocks.
+        //
+        // This mirrors `RefProgramLocation::post_dominator_map`: run
+        // `compute_idom` over the reversed graph, rooted at a virtual node
+        // joining every exit block.
+        const VIRTUAL_EXIT: u64 = u64::max_value();
+        let (out, inn) = graph(&[(1, 2), (2, 3), (2, 4)]);
+        let exits = vec![3, 4];
+
+        let successors = |b: u64| {
+            if b == VIRTUAL_EXIT {
+                return exits.clone();
+            }
+            inn.get(&b).cloned().unwrap_or_else(Vec::new)
+        };
+        let predecessors = |b: u64| {
+            let mut preds = out.get(&b).cloned().unwrap_or_else(Vec::new);
+            if exits.contains(&b) {
+                preds.push(VIRTUAL_EXIT);
+            }
+            preds
+        };
+
+        let rpo = reverse_postorder(VIRTUAL_EXIT, &successors);
+        let idom = compute_idom(VIRTUAL_EXIT, &rpo, &predecessors);
+
+        // Every path out of 1 passes through 2, so 2 post-dominates 1.
+        assert_eq!(idom[&1], 2);
+        // 2 splits to two different exits with no common block before the
+        // virtual exit, so it has no real post-dominator.
+        assert_eq!(idom[&2], VIRTUAL_EXIT);
+        assert_eq!(idom[&3], VIRTUAL_EXIT);
+        assert_eq!(idom[&4], VIRTUAL_EXIT);
+    }
+
+    #[test]
+    fn chop_is_intersection_of_forward_and_backward_reachable() {
+        // 0 -> 1 -> 2 -> 4 is the only path from 0 to 4; 1 -> 3 is a side
+        // branch that never reaches the sink and must be excluded.
+        let function = chop_function();
+
+        let source = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(0).unwrap()));
+        let sink = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(4).unwrap()));
+
+        let chop = chop(&source, &sink).unwrap();
+
+        let expected: HashSet<ProgramLocation> = [
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::EmptyBlock(function.block(0).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::Edge(function.edge(0, 1).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::EmptyBlock(function.block(1).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::Edge(function.edge(1, 2).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::EmptyBlock(function.block(2).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::Edge(function.edge(2, 4).unwrap())),
+            RefProgramLocation::new(&function,
+                RefFunctionLocation::EmptyBlock(function.block(4).unwrap())),
+        ].iter().cloned().map(ProgramLocation::from).collect();
+
+        assert_eq!(chop, expected);
+
+        // The side branch through block 3 never reaches the sink, so none
+        // of it belongs in the chop.
+        let side_edge = ProgramLocation::from(RefProgramLocation::new(&function,
+            RefFunctionLocation::Edge(function.edge(1, 3).unwrap())));
+        let side_block = ProgramLocation::from(RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(3).unwrap())));
+        assert!(!chop.contains(&side_edge));
+        assert!(!chop.contains(&side_block));
+    }
+
+    #[test]
+    fn advance_backward_walks_edges_into_a_block() {
+        let function = diamond_function();
+
+        let location = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(3).unwrap()));
+
+        let predecessors: HashSet<FunctionLocation> = location.advance_backward().unwrap()
+            .into_iter()
+            .map(|location| location.function_location().clone().into())
+            .collect();
+
+        let expected: HashSet<FunctionLocation> = [
+            FunctionLocation::Edge(1, 3),
+            FunctionLocation::Edge(2, 3),
+        ].iter().cloned().collect();
+
+        assert_eq!(predecessors, expected);
+    }
+
+    #[test]
+    fn dominates_rejects_edge_with_a_sibling() {
+        let function = diamond_function();
+
+        let edge_0_1 = RefProgramLocation::new(&function,
+            RefFunctionLocation::Edge(function.edge(0, 1).unwrap()));
+        let block_3 = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(3).unwrap()));
+
+        // Block 0 has two successors, so the edge 0 -> 1 does not dominate
+        // block 3: the path 0 -> 2 -> 3 never takes this edge.
+        assert!(!edge_0_1.dominates(&block_3).unwrap());
+        // It still dominates itself.
+        assert!(edge_0_1.dominates(&edge_0_1).unwrap());
+
+        // A block-level location is unaffected by the fix: block 0 really
+        // does dominate block 3.
+        let block_0 = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(0).unwrap()));
+        assert!(block_0.dominates(&block_3).unwrap());
+    }
+
+    #[test]
+    fn post_dominates_rejects_edge_with_a_sibling() {
+        let function = diamond_function();
+
+        let edge_0_1 = RefProgramLocation::new(&function,
+            RefFunctionLocation::Edge(function.edge(0, 1).unwrap()));
+        let block_0 = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(0).unwrap()));
+
+        // Block 0 has two successors, so the edge 0 -> 1 does not
+        // post-dominate block 0: the path 0 -> 2 -> 3 never takes this edge.
+        assert!(!edge_0_1.post_dominates(&block_0).unwrap());
+        assert!(edge_0_1.post_dominates(&edge_0_1).unwrap());
+
+        // Block 3 really does post-dominate block 0: every path from 0
+        // passes through 3.
+        let block_3 = RefProgramLocation::new(&function,
+            RefFunctionLocation::EmptyBlock(function.block(3).unwrap()));
+        assert!(block_3.post_dominates(&block_0).unwrap());
+    }
+
+    #[test]
+    fn immediate_dominator_of_edge_leaving_an_empty_block() {
+        let function = diamond_function();
+
+        let edge_0_1 = RefProgramLocation::new(&function,
+            RefFunctionLocation::Edge(function.edge(0, 1).unwrap()));
+
+        let idom = edge_0_1.immediate_dominator().unwrap().unwrap();
+
+        assert_eq!(idom.function_location(),
+            &RefFunctionLocation::EmptyBlock(function.block(0).unwrap()));
+    }
+
+    #[test]
+    fn is_before_follows_control_flow_not_index_order() {
+        let program = {
+            let mut program = Program::new();
+            program.add_function(diamond_function());
+            program
+        };
+        let function = program.function(0).unwrap();
+
+        let block_0: ProgramLocation = RefProgramLocation::new(function,
+            RefFunctionLocation::EmptyBlock(function.block(0).unwrap())).into();
+        let block_1: ProgramLocation = RefProgramLocation::new(function,
+            RefFunctionLocation::EmptyBlock(function.block(1).unwrap())).into();
+        let block_2: ProgramLocation = RefProgramLocation::new(function,
+            RefFunctionLocation::EmptyBlock(function.block(2).unwrap())).into();
+        let block_3: ProgramLocation = RefProgramLocation::new(function,
+            RefFunctionLocation::EmptyBlock(function.block(3).unwrap())).into();
+
+        assert!(block_0.is_before(&block_3, &program).unwrap());
+        // Block 1 sorts before block 2, but they are sibling branches of
+        // the diamond, so neither reaches the other.
+        assert!(block_1 < block_2);
+        assert!(!block_1.is_before(&block_2, &program).unwrap());
+        // Block 2 is not reachable from block 3 at all (3 is the join).
+        assert!(!block_3.is_before(&block_2, &program).unwrap());
+    }
+
+    #[test]
+    fn function_location_ord_orders_instructions_before_empty_block_before_edges() {
+        let instruction = FunctionLocation::Instruction(0, 0);
+        let empty_block = FunctionLocation::EmptyBlock(0);
+        let edge = FunctionLocation::Edge(0, 1);
+
+        assert!(instruction < empty_block);
+        assert!(empty_block < edge);
+        assert!(instruction < edge);
+    }
+
+    #[test]
+    fn advance_forward_call_resolves_cross_function_entry() {
+        let program = call_program();
+        let caller = program.function(0).unwrap();
+
+        let caller_entry = caller.block(0).unwrap();
+        let caller_instructions = caller_entry.instructions();
+        let call_site = RefProgramLocation::new(caller,
+            RefFunctionLocation::Instruction(caller_entry, &caller_instructions[0]));
+
+        let call_advance = call_site.advance_forward_call(&program).unwrap().unwrap();
+
+        assert_eq!(call_advance.callee_entry().function().index(), Some(1));
+    }
 }
\ No newline at end of file